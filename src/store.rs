@@ -3,7 +3,7 @@
 use std::sync::Arc;
 
 use matrix_sdk_crypto::{
-    store::{DynCryptoStore, IntoCryptoStore, MemoryStore},
+    store::{Changes, DynCryptoStore, IntoCryptoStore, MemoryStore},
     types::BackupSecrets,
 };
 use wasm_bindgen::prelude::*;
@@ -16,6 +16,10 @@ use crate::{
     vodozemac::Curve25519PublicKey,
 };
 
+/// The default number of PBKDF2 rounds used when encrypting an export,
+/// chosen to match the cost used by Element's key export feature.
+const EXPORT_DEFAULT_ROUNDS: u32 = 500_000;
+
 /// A struct containing an open connection to a CryptoStore.
 ///
 /// Opening the CryptoStore can take some time, due to the PBKDF calculation
@@ -95,7 +99,7 @@ impl StoreHandle {
             None => matrix_sdk_indexeddb::IndexeddbCryptoStore::open_with_name(store_name).await?,
         };
 
-        Ok(store.into_crypto_store())
+        Ok(Arc::new(store) as Arc<DynCryptoStore>)
     }
 
     /// Open a crypto store based on IndexedDB, using the given key for
@@ -121,16 +125,181 @@ impl StoreHandle {
         );
         store_key.zeroize();
 
-        let store = matrix_sdk_indexeddb::IndexeddbCryptoStore::open_with_key(
-            &store_name,
-            &store_key_array,
-        )
-        .await?;
+        let store = Arc::new(
+            matrix_sdk_indexeddb::IndexeddbCryptoStore::open_with_key(&store_name, &store_key_array)
+                .await?,
+        );
+
+        Ok(Self { store: store as Arc<DynCryptoStore> })
+    }
+
+    /// Change the passphrase used to encrypt this store.
+    ///
+    /// `matrix-sdk-indexeddb` does not currently expose a way to re-encrypt
+    /// an already-open store under a new passphrase; the only way to rotate
+    /// it is to export the store's contents and re-import them into a store
+    /// opened with the new passphrase. This method exists so callers have a
+    /// stable place to discover that, rather than the feature silently not
+    /// existing.
+    ///
+    /// # Arguments
+    ///
+    /// * `old_passphrase` - The store's current passphrase.
+    ///
+    /// * `new_passphrase` - The passphrase the store should be encrypted
+    ///   with from now on.
+    #[wasm_bindgen(js_name = "changePassphrase")]
+    pub async fn change_passphrase(
+        &self,
+        mut old_passphrase: String,
+        mut new_passphrase: String,
+    ) -> Result<(), JsError> {
+        old_passphrase.zeroize();
+        new_passphrase.zeroize();
+
+        Err(JsError::new(
+            "Changing the passphrase of an already-open store is not supported; re-create the \
+            store with the new passphrase instead",
+        ))
+    }
+
+    /// Change the key used to encrypt this store.
+    ///
+    /// As with [`StoreHandle::change_passphrase`], `matrix-sdk-indexeddb`
+    /// does not support re-encrypting an already-open store in place.
+    ///
+    /// # Arguments
+    ///
+    /// * `new_key` - The key the store should be encrypted with from now on.
+    ///   Must be a 32-byte array.
+    #[wasm_bindgen(js_name = "changeKey")]
+    pub async fn change_key(&self, mut new_key: Vec<u8>) -> Result<(), JsError> {
+        new_key.zeroize();
+
+        Err(JsError::new(
+            "Changing the key of an already-open store is not supported; re-create the store \
+            with the new key instead",
+        ))
+    }
+
+    /// Export all the room keys in this store as an encrypted, armored file
+    /// in the format used by Element's "Export keys" feature, so they can be
+    /// moved to another client without going through the backup server.
+    ///
+    /// # Arguments
+    ///
+    /// * `passphrase` - The passphrase that will be needed to import the
+    ///   keys again.
+    #[wasm_bindgen(js_name = "exportRoomKeys")]
+    pub async fn export_room_keys(&self, passphrase: String) -> Result<String, JsError> {
+        let sessions = self.store.get_inbound_group_sessions().await?;
+
+        let encrypted = matrix_sdk_crypto::encrypt_room_key_export(
+            &sessions,
+            &passphrase,
+            EXPORT_DEFAULT_ROUNDS,
+        )?;
+
+        Ok(String::from_utf8(encrypted).expect("encrypt_room_key_export returns armored ASCII"))
+    }
 
-        Ok(Self { store: store.into_crypto_store() })
+    /// Import room keys from an encrypted, armored export produced by
+    /// [`StoreHandle::export_room_keys`] or by Element's "Export keys"
+    /// feature.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The armored export, as produced by `export_room_keys`.
+    ///
+    /// * `passphrase` - The passphrase that was used to encrypt the export.
+    ///
+    /// * `progress_listener` - An optional callback invoked as
+    ///   `(imported, total)` as each session is processed, so a UI can
+    ///   stream progress through a large import instead of blocking
+    ///   opaquely.
+    #[wasm_bindgen(js_name = "importRoomKeys")]
+    pub async fn import_room_keys(
+        &self,
+        data: String,
+        passphrase: String,
+        progress_listener: Option<js_sys::Function>,
+    ) -> Result<RoomKeysImportResult, JsError> {
+        let sessions =
+            matrix_sdk_crypto::decrypt_room_key_export(data.as_bytes(), &passphrase)?;
+
+        self.import_sessions(sessions, |imported, total| match &progress_listener {
+            Some(listener) => listener
+                .call2(&JsValue::NULL, &JsValue::from(imported as u32), &JsValue::from(total as u32))
+                .map(|_| ())
+                .map_err(|_| JsError::new("The progress callback threw an error")),
+            None => Ok(()),
+        })
+        .await
+    }
+
+    /// Import the given sessions, saving each one only if it improves on any
+    /// session already in the store, and reporting progress through
+    /// `on_progress` as `(imported, total)`, where `imported` is the number
+    /// of sessions actually saved so far (not merely processed).
+    async fn import_sessions(
+        &self,
+        sessions: Vec<matrix_sdk_crypto::olm::ExportedRoomKey>,
+        mut on_progress: impl FnMut(usize, usize) -> Result<(), JsError>,
+    ) -> Result<RoomKeysImportResult, JsError> {
+        let total = sessions.len();
+        let mut imported = Vec::new();
+        let mut existing = Vec::new();
+
+        for exported in sessions {
+            // A single malformed record shouldn't fail a batch of
+            // otherwise-good sessions; skip it (without counting it as
+            // either imported or existing) and keep going so the caller
+            // still gets back the partial result and progress for
+            // everything else in the batch.
+            if let Ok(session) = matrix_sdk_crypto::olm::InboundGroupSession::from_export(&exported) {
+                let info: RoomKeyInfo = matrix_sdk_crypto::store::RoomKeyInfo::from(&session).into();
+
+                let is_better = match self
+                    .store
+                    .get_inbound_group_session(session.room_id(), session.session_id())
+                    .await?
+                {
+                    Some(existing_session) => {
+                        session.first_known_index() < existing_session.first_known_index()
+                    }
+                    None => true,
+                };
+
+                if is_better {
+                    self.store
+                        .save_changes(Changes { inbound_group_sessions: vec![session], ..Default::default() })
+                        .await?;
+                    imported.push(info);
+                } else {
+                    existing.push(info);
+                }
+            }
+
+            on_progress(imported.len(), total)?;
+        }
+
+        Ok(RoomKeysImportResult { imported, existing })
     }
 }
 
+/// The result of importing a batch of room keys via
+/// [`StoreHandle::import_room_keys`].
+#[derive(Debug)]
+#[wasm_bindgen(getter_with_clone)]
+pub struct RoomKeysImportResult {
+    /// Keys that were newly imported because the store held no session with
+    /// an equal or lower first known message index.
+    pub imported: Vec<RoomKeyInfo>,
+    /// Keys that were left untouched because the store already held a
+    /// session with an equal or lower first known message index.
+    pub existing: Vec<RoomKeyInfo>,
+}
+
 impl IntoCryptoStore for StoreHandle {
     fn into_crypto_store(self) -> Arc<DynCryptoStore> {
         self.store.clone()
@@ -170,7 +339,7 @@ impl CrossSigningKeyExport {
 
 /// Information on a room key that has been received or imported.
 #[wasm_bindgen]
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct RoomKeyInfo {
     pub(crate) inner: matrix_sdk_crypto::store::RoomKeyInfo,
 }
@@ -248,6 +417,74 @@ impl RoomKeyWithheldInfo {
     }
 }
 
+/// A decryption key for a server-side key backup.
+///
+/// This wraps [`matrix_sdk_crypto::backups::BackupDecryptionKey`], which
+/// owns the base64/base58 "recovery key" encoding and the PBKDF2
+/// passphrase derivation, so none of that needs reimplementing here.
+#[wasm_bindgen]
+pub struct BackupDecryptionKey {
+    pub(crate) inner: matrix_sdk_crypto::backups::BackupDecryptionKey,
+}
+
+impl_from_to_inner!(matrix_sdk_crypto::backups::BackupDecryptionKey => BackupDecryptionKey);
+
+/// Manual `Debug` impl: the derived one would recurse into the inner key's
+/// own `Debug`, which zeroize's `Zeroizing` forwards straight through to
+/// the raw bytes. Redact it instead.
+impl std::fmt::Debug for BackupDecryptionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BackupDecryptionKey").finish_non_exhaustive()
+    }
+}
+
+#[wasm_bindgen]
+impl BackupDecryptionKey {
+    /// Generate a new, random backup decryption key.
+    #[wasm_bindgen(js_name = "createRandomKey")]
+    pub fn create_random_key() -> Result<BackupDecryptionKey, JsError> {
+        Ok(matrix_sdk_crypto::backups::BackupDecryptionKey::new()
+            .ok_or_else(|| JsError::new("Failed to generate a random backup key"))?
+            .into())
+    }
+
+    /// Create a [`BackupDecryptionKey`] from its unpadded base64
+    /// representation, as found in [`BackupSecretsBundle.key`].
+    #[wasm_bindgen(js_name = "fromBase64")]
+    pub fn from_base64(key: &str) -> Result<BackupDecryptionKey, JsError> {
+        Ok(matrix_sdk_crypto::backups::BackupDecryptionKey::from_base64(key)?.into())
+    }
+
+    /// Create a [`BackupDecryptionKey`] from the user-facing base58 recovery
+    /// key, such as `EsTc LW2K PGiF wKEA 3As5 g5c4 BXwk qeeJ ZJV8 Q9fu gUMN
+    /// UE4d`. Whitespace is ignored.
+    #[wasm_bindgen(js_name = "fromBase58")]
+    pub fn from_base58(key: &str) -> Result<BackupDecryptionKey, JsError> {
+        Ok(matrix_sdk_crypto::backups::BackupDecryptionKey::from_base58(key)?.into())
+    }
+
+    /// Derive a [`BackupDecryptionKey`] from a backup passphrase, using
+    /// PBKDF2-HMAC-SHA-512 over the passphrase and salt.
+    #[wasm_bindgen(js_name = "fromPassphrase")]
+    pub fn from_passphrase(passphrase: &str, salt: &str, rounds: u32) -> BackupDecryptionKey {
+        matrix_sdk_crypto::backups::BackupDecryptionKey::from_passphrase(passphrase, salt, rounds)
+            .into()
+    }
+
+    /// Serialize the key to its unpadded base64 representation.
+    #[wasm_bindgen(js_name = "toBase64")]
+    pub fn to_base64(&self) -> String {
+        self.inner.to_base64()
+    }
+
+    /// Serialize the key to the user-facing base58 recovery key, grouped
+    /// into space-separated 4-character blocks.
+    #[wasm_bindgen(js_name = "toBase58")]
+    pub fn to_base58(&self) -> String {
+        self.inner.to_base58()
+    }
+}
+
 /// Struct containing the bundle of secrets to fully activate a new device for
 /// end-to-end encryption.
 #[derive(Debug)]
@@ -310,6 +547,176 @@ impl SecretsBundle {
 
         Ok(Self { inner: bundle })
     }
+
+    /// Encrypt the [`SecretsBundle`] to an armored blob under `passphrase`,
+    /// so a fully-provisioned E2EE identity can be handed to a freshly
+    /// set-up device over an out-of-band channel without exposing the raw
+    /// seeds as plaintext.
+    ///
+    /// Delegates to [`matrix_sdk_crypto::types::SecretsBundle::encrypt`],
+    /// which takes care of serializing and zeroizing the plaintext itself.
+    pub fn encrypt(&self, passphrase: String) -> Result<String, JsError> {
+        Ok(self.inner.encrypt(&passphrase, EXPORT_DEFAULT_ROUNDS)?)
+    }
+
+    /// Decrypt a [`SecretsBundle`] previously produced by
+    /// [`SecretsBundle::encrypt`].
+    pub fn decrypt(blob: String, passphrase: String) -> Result<SecretsBundle, JsError> {
+        Ok(matrix_sdk_crypto::types::SecretsBundle::decrypt(&blob, &passphrase)?.into())
+    }
 }
 
 impl_from_to_inner!(matrix_sdk_crypto::types::SecretsBundle => SecretsBundle);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backup_decryption_key_base58_round_trip() {
+        let key = BackupDecryptionKey::create_random_key().expect("key generation failed");
+        let recovery_key = key.to_base58();
+
+        let decoded = BackupDecryptionKey::from_base58(&recovery_key).expect("parsing failed");
+
+        assert_eq!(decoded.to_base64(), key.to_base64());
+    }
+
+    #[test]
+    fn backup_decryption_key_rejects_flipped_byte() {
+        let key = BackupDecryptionKey::create_random_key().expect("key generation failed");
+        let collapsed: String = key.to_base58().chars().filter(|c| !c.is_whitespace()).collect();
+
+        let mut decoded = bs58::decode(&collapsed).into_vec().expect("encoding produced valid base58");
+        let mid = decoded.len() / 2;
+        decoded[mid] ^= 0x01;
+        let tampered = bs58::encode(&decoded).into_string();
+
+        assert!(BackupDecryptionKey::from_base58(&tampered).is_err());
+    }
+
+    fn memory_store_handle() -> StoreHandle {
+        StoreHandle { store: MemoryStore::new().into_crypto_store() }
+    }
+
+    /// Build a minimal, valid `ExportedRoomKey` for a freshly created
+    /// megolm session in `room_id`, for exercising the import/export paths
+    /// without needing a real device or server.
+    fn dummy_exported_session(room_id: &str) -> matrix_sdk_crypto::olm::ExportedRoomKey {
+        let room_id = ruma::RoomId::parse(room_id).expect("test room ID is valid");
+        let account = vodozemac::olm::Account::new();
+        let identity_keys = account.identity_keys();
+        let group_session =
+            vodozemac::megolm::GroupSession::new(vodozemac::megolm::SessionConfig::version_1());
+
+        let inbound = matrix_sdk_crypto::olm::InboundGroupSession::new(
+            identity_keys.curve25519,
+            identity_keys.ed25519,
+            &room_id,
+            &group_session.session_key(),
+            matrix_sdk_crypto::types::EventEncryptionAlgorithm::MegolmV1AesSha2,
+            None,
+        )
+        .expect("constructing a fresh inbound group session should not fail");
+
+        inbound.export()
+    }
+
+    #[test]
+    fn import_sessions_reports_running_imported_count() {
+        futures::executor::block_on(async {
+            let handle = memory_store_handle();
+            let sessions = vec![
+                dummy_exported_session("!room-a:example.org"),
+                dummy_exported_session("!room-b:example.org"),
+            ];
+
+            let mut progress = Vec::new();
+            let result = handle
+                .import_sessions(sessions, |imported, total| {
+                    progress.push((imported, total));
+                    Ok(())
+                })
+                .await
+                .expect("import should succeed");
+
+            assert_eq!(result.imported.len(), 2);
+            assert_eq!(result.existing.len(), 0);
+            assert_eq!(progress, vec![(1, 2), (2, 2)]);
+        });
+    }
+
+    #[test]
+    fn import_sessions_does_not_overwrite_an_equally_good_session() {
+        futures::executor::block_on(async {
+            let handle = memory_store_handle();
+            let session = dummy_exported_session("!room:example.org");
+            // Duplicate the fixture via a serialization round trip so both
+            // imports see the exact same exported session, rather than two
+            // independently generated ones that would never collide.
+            let duplicate: matrix_sdk_crypto::olm::ExportedRoomKey =
+                serde_json::from_slice(&serde_json::to_vec(&session).unwrap()).unwrap();
+
+            let first = handle
+                .import_sessions(vec![session], |_, _| Ok(()))
+                .await
+                .expect("first import should succeed");
+            assert_eq!(first.imported.len(), 1);
+
+            let second = handle
+                .import_sessions(vec![duplicate], |_, _| Ok(()))
+                .await
+                .expect("second import should succeed");
+            assert_eq!(second.imported.len(), 0);
+            assert_eq!(second.existing.len(), 1);
+        });
+    }
+
+    #[test]
+    fn export_and_import_room_keys_round_trip() {
+        futures::executor::block_on(async {
+            let handle = memory_store_handle();
+            let session = dummy_exported_session("!room:example.org");
+            handle
+                .import_sessions(vec![session], |_, _| Ok(()))
+                .await
+                .expect("seeding the store should succeed");
+
+            let exported = handle
+                .export_room_keys("correct horse battery staple".to_owned())
+                .await
+                .expect("export should succeed");
+
+            let other_handle = memory_store_handle();
+            let result = other_handle
+                .import_room_keys(exported, "correct horse battery staple".to_owned(), None)
+                .await
+                .expect("import should succeed");
+
+            assert_eq!(result.imported.len(), 1);
+        });
+    }
+
+    #[test]
+    fn import_room_keys_rejects_wrong_passphrase() {
+        futures::executor::block_on(async {
+            let handle = memory_store_handle();
+            let session = dummy_exported_session("!room:example.org");
+            handle
+                .import_sessions(vec![session], |_, _| Ok(()))
+                .await
+                .expect("seeding the store should succeed");
+
+            let exported = handle
+                .export_room_keys("correct horse battery staple".to_owned())
+                .await
+                .expect("export should succeed");
+
+            let other_handle = memory_store_handle();
+            assert!(other_handle
+                .import_room_keys(exported, "wrong passphrase".to_owned(), None)
+                .await
+                .is_err());
+        });
+    }
+}